@@ -1,10 +1,21 @@
+mod cost;
+mod dsp;
+mod egraph;
+mod equiv;
+mod pipeline;
+
 use clap::Parser;
+use cost::CostModel;
+use dsp::DspShapeFn;
+use egraph::{EClassId, EGraph, ENode};
 use eqmap::netlist::PrimitiveCell;
 use eqmap::pass::{Error, Pass, PrintVerilog};
 use eqmap::register_passes;
 use eqmap::verilog::sv_parse_wrapper;
 use nl_compiler::{from_vast, from_vast_overrides};
-use safety_net::{Identifier, Instantiable, MultiDiGraph, Netlist, SimpleCombDepth, format_id};
+use pipeline::{FixedPointPass, Stage};
+use safety_net::{format_id, Identifier, Instantiable, MultiDiGraph, Netlist, SimpleCombDepth};
+use std::collections::HashMap;
 use std::io::Read;
 use std::path::PathBuf;
 use std::rc::Rc;
@@ -23,16 +34,30 @@ impl Pass for DotGraph {
 /// Clean the netlist
 pub struct Clean;
 
-impl Pass for Clean {
-    type I = PrimitiveCell;
-
-    fn run(&self, netlist: &Rc<Netlist<Self::I>>) -> Result<String, Error> {
+impl Clean {
+    fn clean(&self, netlist: &Rc<Netlist<PrimitiveCell>>) -> Result<(String, bool), Error> {
         let cleaned = netlist.clean()?;
-        Ok(format!(
+        let message = format!(
             "Cleaned {} objects. {} remain.",
             cleaned.len(),
             netlist.len()
-        ))
+        );
+        Ok((message, !cleaned.is_empty()))
+    }
+}
+
+impl Pass for Clean {
+    type I = PrimitiveCell;
+
+    fn run(&self, netlist: &Rc<Netlist<Self::I>>) -> Result<String, Error> {
+        self.clean(netlist).map(|(message, _)| message)
+    }
+}
+
+impl pipeline::FixedPointPass for Clean {
+    fn run_tracked(&self, netlist: &Rc<Netlist<Self::I>>) -> Result<pipeline::PassOutcome, Error> {
+        let (message, changed) = self.clean(netlist)?;
+        Ok(pipeline::PassOutcome { message, changed })
     }
 }
 
@@ -62,10 +87,8 @@ impl Pass for DisconnectRegisters {
 /// Disconnect wires based on greedy arc set heuristic
 pub struct DisconnectArcSet;
 
-impl Pass for DisconnectArcSet {
-    type I = PrimitiveCell;
-
-    fn run(&self, netlist: &Rc<Netlist<Self::I>>) -> Result<String, Error> {
+impl DisconnectArcSet {
+    fn disconnect(&self, netlist: &Rc<Netlist<PrimitiveCell>>) -> Result<(String, bool), Error> {
         let mut i = 0;
         let analysis = netlist.get_analysis::<MultiDiGraph<_>>()?;
 
@@ -74,7 +97,22 @@ impl Pass for DisconnectArcSet {
             i += 1;
         }
 
-        Ok(format!("Disconnected {i} arcs"))
+        Ok((format!("Disconnected {i} arcs"), i > 0))
+    }
+}
+
+impl Pass for DisconnectArcSet {
+    type I = PrimitiveCell;
+
+    fn run(&self, netlist: &Rc<Netlist<Self::I>>) -> Result<String, Error> {
+        self.disconnect(netlist).map(|(message, _)| message)
+    }
+}
+
+impl pipeline::FixedPointPass for DisconnectArcSet {
+    fn run_tracked(&self, netlist: &Rc<Netlist<Self::I>>) -> Result<pipeline::PassOutcome, Error> {
+        let (message, changed) = self.disconnect(netlist)?;
+        Ok(pipeline::PassOutcome { message, changed })
     }
 }
 
@@ -145,9 +183,368 @@ impl Pass for ReportDepth {
     }
 }
 
+/// Primitive type names recognized when lifting a netlist into the e-graph.
+/// Anything else (registers, macros, unrecognized primitives) is left as an
+/// opaque input to the cones that feed it.
+fn gate_op(type_name: &str) -> Option<&'static str> {
+    match type_name {
+        "AND" | "AND2" => Some("and"),
+        "OR" | "OR2" => Some("or"),
+        "XOR" | "XOR2" => Some("xor"),
+        "INV" | "NOT" => Some("not"),
+        "MUX" | "MUX2" => Some("mux"),
+        _ => None,
+    }
+}
+
+/// Lifts every instance of `netlist` into `eg`, returning the e-class
+/// assigned to each instance name (i.e. to the net it drives). Cells whose type isn't one of the recognized boolean primitives are
+/// lifted as opaque `ENode::Input`s so the surrounding logic still
+/// saturates even over a netlist eqmap doesn't fully understand.
+fn lift(netlist: &Rc<Netlist<PrimitiveCell>>, eg: &mut EGraph) -> HashMap<Identifier, EClassId> {
+    let mut classes: HashMap<Identifier, EClassId> = HashMap::new();
+
+    for inst in netlist.matches(|_| true) {
+        let name = inst.get_instance_name().unwrap_or_default();
+        let class = match gate_op(inst.type_name()) {
+            Some("and") | Some("or") | Some("xor") if inst.inputs().len() < 2 => {
+                eg.add(ENode::Input(name.clone()))
+            }
+            Some("mux") if inst.inputs().len() < 3 => eg.add(ENode::Input(name.clone())),
+            Some("and") => {
+                let ins = net_classes(&inst, eg, &mut classes);
+                eg.add(ENode::And(ins[0], ins[1]))
+            }
+            Some("or") => {
+                let ins = net_classes(&inst, eg, &mut classes);
+                eg.add(ENode::Or(ins[0], ins[1]))
+            }
+            Some("xor") => {
+                let ins = net_classes(&inst, eg, &mut classes);
+                eg.add(ENode::Xor(ins[0], ins[1]))
+            }
+            Some("not") => {
+                let ins = net_classes(&inst, eg, &mut classes);
+                eg.add(ENode::Not(ins[0]))
+            }
+            Some("mux") => {
+                let ins = net_classes(&inst, eg, &mut classes);
+                eg.add(ENode::Mux(ins[0], ins[1], ins[2]))
+            }
+            _ => eg.add(ENode::Input(name.clone())),
+        };
+        // `net_classes` may have already lifted a consumer of `name` before
+        // its producer was visited, inserting a placeholder `Input` class
+        // for it. Union that placeholder into the real class rather than
+        // overwriting it, or the placeholder is left stranded.
+        if let Some(&placeholder) = classes.get(&name) {
+            eg.union(placeholder, class);
+        }
+        classes.insert(name, class);
+    }
+
+    classes
+}
+
+/// Resolves each input pin of `inst` to the e-class of the net driving it.
+/// A driver that hasn't been visited yet gets a placeholder `Input` class
+/// (which `lift` unions with the real one later); an undriven pin maps to
+/// the `Const(false)` class.
+fn net_classes(
+    inst: &impl Instantiable<I = PrimitiveCell>,
+    eg: &mut EGraph,
+    classes: &mut HashMap<Identifier, EClassId>,
+) -> Vec<EClassId> {
+    inst.inputs()
+        .map(|pin| {
+            let driver = pin.driver();
+            match driver.and_then(|d| d.get_instance_name()) {
+                Some(name) => *classes
+                    .entry(name.clone())
+                    .or_insert_with(|| eg.add(ENode::Input(name))),
+                None => eg.add(ENode::Const(false)),
+            }
+        })
+        .collect()
+}
+
+/// Resolves an e-class to the net identifier it should be rebuilt under:
+/// the original driver name if one survived lifting, otherwise a fresh
+/// synthetic name for an e-class that no lifted instance is named after
+/// (e.g. the constant `net_classes` uses for an undriven input pin).
+fn net_name(id: EClassId, class_name: &HashMap<EClassId, Identifier>) -> Identifier {
+    class_name
+        .get(&id)
+        .cloned()
+        .unwrap_or_else(|| format_id!("eqsat_{id}"))
+}
+
+/// Whether `name` already resolves to an instance with the same type and
+/// operands that extraction is about to (re)build. Used to keep `rebuilt`
+/// (and `changed`) honest: re-synthesizing a gate that's byte-identical to
+/// what's already there isn't a real change, and would otherwise make
+/// `EqSat` never reach a fixed point in a looped pipeline group.
+fn already_matches(
+    netlist: &Rc<Netlist<PrimitiveCell>>,
+    name: &Identifier,
+    op: &str,
+    operands: &[Identifier],
+) -> bool {
+    let Some(inst) = netlist
+        .matches(|i| i.get_instance_name().as_ref() == Some(name))
+        .next()
+    else {
+        return false;
+    };
+    if inst.type_name() != op {
+        return false;
+    }
+    let current: Vec<Identifier> = inst
+        .inputs()
+        .filter_map(|p| p.driver().and_then(|d| d.get_instance_name()))
+        .collect();
+    current == operands
+}
+
+fn operand_classes(node: &ENode) -> Vec<EClassId> {
+    match *node {
+        ENode::Const(_) | ENode::Input(_) => vec![],
+        ENode::Not(a) => vec![a],
+        ENode::And(a, b) | ENode::Or(a, b) | ENode::Xor(a, b) => vec![a, b],
+        ENode::Mux(s, a, b) => vec![s, a, b],
+    }
+}
+
+/// Equality-saturation rewrite+extraction over the boolean/mux primitives.
+///
+/// Lifts the netlist into an e-graph, saturates it against a small rule set
+/// (constant folding, idempotence, double negation, MUX simplification; no
+/// associativity or De Morgan rewrites), runs DAG-aware extraction so shared sub-circuits are
+/// only rebuilt once, and instantiates the winning e-nodes as
+/// `PrimitiveCell`s. Primary I/O identifiers are preserved so `verify()`
+/// still holds afterwards.
+pub struct EqSat;
+
+impl EqSat {
+    const MAX_ITERS: usize = 32;
+    const NODE_BUDGET: usize = 1_000_000;
+}
+
+impl EqSat {
+    fn saturate_and_rebuild(
+        &self,
+        netlist: &Rc<Netlist<PrimitiveCell>>,
+    ) -> Result<(String, bool), Error> {
+        let mut eg = EGraph::new();
+        let classes = lift(netlist, &mut eg);
+
+        let iters = eg.saturate(Self::MAX_ITERS, Self::NODE_BUDGET);
+
+        // `classes` holds ids as of lift time; saturation may have since
+        // unioned some of them into another class, so canonicalize before
+        // using them to index into the extraction result.
+        let roots: Vec<EClassId> = classes.values().map(|&id| eg.find(id)).collect();
+        let model = cost::GateAreaCost;
+        let extracted = egraph::dag_extract(&eg, &roots, |n| model.node_cost(n));
+
+        // Seed with primary I/O names first so they always win ties when
+        // multiple names collapse into the same e-class (e.g. `out = a & a`
+        // unions `out` with `a`) — `classes` is a `HashMap`, so iterating it
+        // directly would pick an arbitrary, run-to-run-unstable name and
+        // could silently rename a primary output away.
+        let mut class_name: HashMap<EClassId, Identifier> = HashMap::new();
+        for io_name in netlist.primary_inputs().chain(netlist.primary_outputs()) {
+            if let Some(&id) = classes.get(&io_name) {
+                class_name.entry(eg.find(id)).or_insert(io_name);
+            }
+        }
+        for (name, &id) in &classes {
+            class_name
+                .entry(eg.find(id))
+                .or_insert_with(|| name.clone());
+        }
+
+        let mut rebuilt = 0;
+        for (id, node) in &extracted {
+            if matches!(node, ENode::Const(_) | ENode::Input(_)) {
+                continue; // already a real net, nothing to instantiate
+            }
+            let name = net_name(*id, &class_name);
+            let op = match node {
+                ENode::And(..) => "AND",
+                ENode::Or(..) => "OR",
+                ENode::Xor(..) => "XOR",
+                ENode::Not(..) => "INV",
+                ENode::Mux(..) => "MUX",
+                ENode::Const(_) | ENode::Input(_) => unreachable!(),
+            };
+            let operands: Vec<Identifier> = operand_classes(node)
+                .into_iter()
+                .map(|c| net_name(c, &class_name))
+                .collect();
+
+            if already_matches(netlist, &name, op, &operands) {
+                continue;
+            }
+            netlist.build_primitive(op, name, &operands)?;
+            rebuilt += 1;
+        }
+
+        let message = format!(
+            "EqSat: saturated in {iters} iterations, {} e-classes, {rebuilt} cells rebuilt from {} extracted DAG nodes",
+            eg.num_classes(),
+            extracted.len()
+        );
+        Ok((message, rebuilt > 0))
+    }
+}
+
+impl Pass for EqSat {
+    type I = PrimitiveCell;
+
+    fn run(&self, netlist: &Rc<Netlist<Self::I>>) -> Result<String, Error> {
+        self.saturate_and_rebuild(netlist)
+            .map(|(message, _)| message)
+    }
+}
+
+impl pipeline::FixedPointPass for EqSat {
+    fn run_tracked(&self, netlist: &Rc<Netlist<Self::I>>) -> Result<pipeline::PassOutcome, Error> {
+        let (message, changed) = self.saturate_and_rebuild(netlist)?;
+        Ok(pipeline::PassOutcome { message, changed })
+    }
+}
+
+/// The macro shape `DspMap` targets. Swap this for a different function of
+/// [`dsp::DspShapeFn`] to retarget a different vendor DSP primitive, the
+/// same mechanism `xilinx_overrides` uses for port renaming at parse time.
+const DSP_SHAPE: DspShapeFn = dsp::default_shape;
+
+/// Macro/DSP technology mapping: recognizes `a*b`, `a*b+c`, and pre-adder
+/// `(a+d)*b+c` cones and replaces each with a single coarse-grained macro
+/// instance, turning eqmap from a purely gate-level tool into one that can
+/// target vendor DSP blocks.
+pub struct DspMap;
+
+impl Pass for DspMap {
+    type I = PrimitiveCell;
+
+    fn run(&self, netlist: &Rc<Netlist<Self::I>>) -> Result<String, Error> {
+        let analysis = netlist.get_analysis::<MultiDiGraph<_>>()?;
+        let macros = dsp::find_macros(netlist, &analysis);
+
+        let mut mapped = 0;
+        for pat in &macros {
+            let shape = DSP_SHAPE(pat);
+            let bindings = shape.bindings(pat);
+            netlist.build_primitive_named(
+                shape.type_name,
+                shape.port_out.clone(),
+                pat.output.clone(),
+                &bindings,
+            )?;
+
+            for consumed in &pat.consumed {
+                let replaced = netlist
+                    .matches(|i| i.get_instance_name().as_ref() == Some(consumed))
+                    .next();
+                if let Some(inst) = replaced {
+                    for input in inst.inputs() {
+                        input.disconnect();
+                    }
+                }
+            }
+
+            mapped += 1;
+        }
+
+        Ok(format!(
+            "Mapped {mapped} multiply-add cones onto DSP macros"
+        ))
+    }
+}
+
+/// Proves (up to a bounded exhaustive check) that the netlist is
+/// combinationally equivalent to the reference design at `golden`, so an
+/// `EqSat`/`DspMap` optimization flow can be smoke-tested end to end.
+/// Sequential designs are cut at register boundaries: a register's output
+/// is treated as a free input and its input as a checked output, rather
+/// than being modeled across clock cycles. Only AND/OR/XOR/INV/MUX cells are
+/// evaluated; any other cell (e.g. a `DSP` macro, `MUL` or `ADD`) evaluates
+/// to constant 0 in both netlists, so the logic it implements is not checked.
+pub struct CheckEquiv {
+    golden: PathBuf,
+}
+
+impl CheckEquiv {
+    pub fn new(golden: PathBuf) -> Self {
+        Self { golden }
+    }
+}
+
+fn pass_error(e: impl std::fmt::Display) -> Error {
+    Error::IoError(std::io::Error::other(e.to_string()))
+}
+
+impl Pass for CheckEquiv {
+    type I = PrimitiveCell;
+
+    fn run(&self, netlist: &Rc<Netlist<Self::I>>) -> Result<String, Error> {
+        let src = std::fs::read_to_string(&self.golden).map_err(pass_error)?;
+        let ast = sv_parse_wrapper(&src, Some(self.golden.clone())).map_err(pass_error)?;
+        let golden = from_vast(&ast).map_err(pass_error)?;
+
+        let message = match equiv::check(netlist, &golden) {
+            equiv::Verdict::Equivalent { assignments_checked } => format!(
+                "CheckEquiv: equivalent to {} over {assignments_checked} input assignments",
+                self.golden.display()
+            ),
+            equiv::Verdict::Counterexample { output, assignment } => {
+                let bits: Vec<String> = assignment
+                    .iter()
+                    .map(|(name, value)| format!("{name}={}", *value as u8))
+                    .collect();
+                format!(
+                    "CheckEquiv: INEQUIVALENT at output '{output}' for {}",
+                    bits.join(", ")
+                )
+            }
+            equiv::Verdict::TooLarge { free_inputs } => format!(
+                "CheckEquiv: {free_inputs} free inputs exceeds the {} this smoketest can exhaustively cover",
+                equiv::MAX_FREE_INPUTS
+            ),
+            equiv::Verdict::PortMismatch {
+                inputs_only_in_netlist,
+                inputs_only_in_golden,
+                outputs_only_in_netlist,
+                outputs_only_in_golden,
+            } => {
+                let join = |names: &[Identifier]| {
+                    names
+                        .iter()
+                        .map(|n| n.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                };
+                format!(
+                    "CheckEquiv: port mismatch against {} (inputs only in netlist: [{}], only in golden: [{}]; \
+                     outputs only in netlist: [{}], only in golden: [{}])",
+                    self.golden.display(),
+                    join(&inputs_only_in_netlist),
+                    join(&inputs_only_in_golden),
+                    join(&outputs_only_in_netlist),
+                    join(&outputs_only_in_golden),
+                )
+            }
+        };
+
+        Ok(message)
+    }
+}
+
 register_passes!(PrimitiveCell; PrintVerilog, DotGraph, Clean, DisconnectRegisters,
                                 DisconnectArcSet, MarkArcSet, RenameNets, ReportSccs,
-                                ReportDepth);
+                                ReportDepth, EqSat, DspMap, CheckEquiv(PathBuf));
 
 /// Netlist optimization debugging tool
 #[derive(Parser, Debug)]
@@ -164,9 +561,28 @@ struct Args {
     #[arg(short = 'v', long, default_value_t = false)]
     verify: bool,
 
-    /// A list of passes to run in order
-    #[arg(value_delimiter = ',', short = 'p', long, value_enum)]
-    passes: Vec<Passes>,
+    /// Maximum rounds for a `(...)*N` pipeline group that omits `*N`
+    #[arg(long, default_value_t = 16)]
+    max_iters: usize,
+
+    /// Stop looping a pipeline group once the netlist grows past this many cells
+    #[arg(long, default_value_t = 1_000_000)]
+    node_budget: usize,
+
+    /// Pipeline of passes to run, e.g. "clean,(eqsat,clean)*3"
+    #[arg(short = 'p', long)]
+    passes: String,
+}
+
+fn parse_pass_token(token: &str) -> Result<Passes, String> {
+    if let Some((name, arg)) = token.split_once('=') {
+        return if name.eq_ignore_ascii_case("checkequiv") {
+            Ok(Passes::CheckEquiv(PathBuf::from(arg)))
+        } else {
+            Err(format!("pass '{name}' does not take an argument"))
+        };
+    }
+    <Passes as clap::ValueEnum>::from_str(token, true).map_err(|e| e.to_string())
 }
 
 fn xilinx_overrides(id: &Identifier, cell: &PrimitiveCell) -> Option<PrimitiveCell> {
@@ -214,27 +630,89 @@ fn main() -> std::io::Result<()> {
         from_vast(&ast).map_err(std::io::Error::other)?
     };
 
-    let n = args.passes.len();
+    let stages = pipeline::parse(&args.passes, parse_pass_token).map_err(std::io::Error::other)?;
+    let n = stages.len();
 
-    for (i, pass) in args.passes.into_iter().enumerate() {
-        eprintln!("INFO: Running pass {i} ({pass})...");
-        let pass_instance = pass.get_pass();
-        match pass_instance.run(&f) {
-            Ok(output) => {
-                if i == n - 1 {
+    for (i, stage) in stages.into_iter().enumerate() {
+        let is_last_stage = i == n - 1;
+        match stage {
+            Stage::Single(pass) => {
+                eprintln!("INFO: Running pass {i} ({pass})...");
+                let (output, _) = run_one(&pass, &f)?;
+                if is_last_stage {
                     f.verify().map_err(std::io::Error::other)?;
-                    println!("{}", output)
+                    println!("{output}");
                 } else {
                     if args.verify {
                         f.verify().map_err(std::io::Error::other)?;
                     }
-                    eprintln!("INFO: {pass}: {}", output)
+                    eprintln!("INFO: {pass}: {output}");
+                }
+            }
+            Stage::Group { passes, max_rounds } => {
+                // `--max-iters` only bounds a group that didn't write its own
+                // `*N`; an explicit `*N` is used as given, even above
+                // `--max-iters`. Either way the group stops early once a round
+                // changes nothing or the netlist grows past `--node-budget`.
+                let cap = if max_rounds == usize::MAX {
+                    args.max_iters
+                } else {
+                    max_rounds
+                };
+                let mut last_output = String::new();
+                let mut round = 0;
+                loop {
+                    if round >= cap || f.len() > args.node_budget {
+                        break;
+                    }
+                    let mut any_changed = false;
+                    for pass in &passes {
+                        eprintln!("INFO: Running pass {i}.{round} ({pass})...");
+                        let (output, changed) = run_one(pass, &f)?;
+                        any_changed |= changed;
+                        if args.verify {
+                            f.verify().map_err(std::io::Error::other)?;
+                        }
+                        eprintln!("INFO: {pass}: {output}");
+                        last_output = output;
+                    }
+                    round += 1;
+                    if !any_changed {
+                        break;
+                    }
+                }
+                if is_last_stage {
+                    f.verify().map_err(std::io::Error::other)?;
+                    println!("{last_output}");
                 }
             }
-            Err(Error::IoError(e)) => return Err(e),
-            Err(e) => return Err(std::io::Error::other(e)),
         }
     }
 
     Ok(())
 }
+
+/// Runs a single pipeline pass, returning its report plus whether it
+/// changed the netlist. Passes that track this ([`Clean`], [`EqSat`],
+/// [`DisconnectArcSet`]) report it precisely; any other pass is assumed to
+/// have changed the netlist so a pipeline group containing it never falsely
+/// looks converged.
+fn run_one(pass: &Passes, netlist: &Rc<Netlist<PrimitiveCell>>) -> std::io::Result<(String, bool)> {
+    let result = match pass {
+        Passes::Clean => {
+            FixedPointPass::run_tracked(&Clean, netlist).map(|o| (o.message, o.changed))
+        }
+        Passes::EqSat => {
+            FixedPointPass::run_tracked(&EqSat, netlist).map(|o| (o.message, o.changed))
+        }
+        Passes::DisconnectArcSet => {
+            FixedPointPass::run_tracked(&DisconnectArcSet, netlist).map(|o| (o.message, o.changed))
+        }
+        other => other.get_pass().run(netlist).map(|message| (message, true)),
+    };
+    match result {
+        Ok(ok) => Ok(ok),
+        Err(Error::IoError(e)) => Err(e),
+        Err(e) => Err(std::io::Error::other(e)),
+    }
+}
@@ -0,0 +1,30 @@
+//! Cost models for e-graph extraction, keyed on the e-node's boolean op.
+//! LUTs, registers, carries and other cells never become e-graph ops (they
+//! are lifted as opaque inputs), so they carry no weight of their own here.
+
+use super::egraph::ENode;
+
+/// Per-op area/delay weight used by [`crate::egraph::dag_extract`]. Kept as
+/// a trait (rather than a bare closure) so alternative technology targets
+/// can plug in their own weights without touching the extraction code.
+pub trait CostModel {
+    /// Cost of instantiating a single e-node of this shape, not counting
+    /// its operands (extraction sums this over the deduplicated DAG).
+    fn node_cost(&self, node: &ENode) -> u32;
+}
+
+/// Area-oriented default: every 2-input primitive costs one gate, `MUX`
+/// costs two (it's usually built from two gates + a select net), and
+/// leaves are free since they aren't instantiated by this pass.
+pub struct GateAreaCost;
+
+impl CostModel for GateAreaCost {
+    fn node_cost(&self, node: &ENode) -> u32 {
+        match node {
+            ENode::Const(_) | ENode::Input(_) => 0,
+            ENode::Not(_) => 1,
+            ENode::And(..) | ENode::Or(..) | ENode::Xor(..) => 1,
+            ENode::Mux(..) => 2,
+        }
+    }
+}
@@ -0,0 +1,246 @@
+//! Macro/DSP pattern matching: folds multiply-add (and pre-adder
+//! multiply-add) cones into a single coarse-grained macro primitive.
+
+use eqmap::netlist::PrimitiveCell;
+use safety_net::{Identifier, Instantiable, MultiDiGraph, Netlist};
+use std::rc::Rc;
+
+fn type_is(inst: &impl Instantiable<I = PrimitiveCell>, names: &[&str]) -> bool {
+    names.contains(&inst.type_name())
+}
+
+/// A recognized `a*b`, `a*b+c`, or `(a+d)*b+c` cone, named by the nets at
+/// its boundary. `consumed` lists the instance names being folded away so
+/// the pass can `disconnect()` them once the macro is wired up.
+pub struct MacroPattern {
+    pub mult_a: Identifier,
+    pub mult_b: Identifier,
+    pub pre_add: Option<Identifier>,
+    pub accumulate: Option<Identifier>,
+    pub output: Identifier,
+    pub consumed: Vec<Identifier>,
+}
+
+/// The target macro's port/shape definition: its primitive type name and
+/// the port each logical operand binds to. Retargeting to a different
+/// vendor DSP primitive is just writing a new function of this shape.
+pub struct DspShape {
+    pub type_name: &'static str,
+    pub port_a: Identifier,
+    pub port_b: Identifier,
+    pub port_c: Identifier,
+    pub port_d: Identifier,
+    pub port_out: Identifier,
+}
+
+impl DspShape {
+    /// Binds `pat`'s operands to this shape's ports, producing the named
+    /// `(port, net)` pairs `build_primitive_named` expects. Ports with no
+    /// corresponding operand (no accumulate, no pre-adder) are omitted.
+    pub fn bindings(&self, pat: &MacroPattern) -> Vec<(Identifier, Identifier)> {
+        let mut out = vec![
+            (self.port_a.clone(), pat.mult_a.clone()),
+            (self.port_b.clone(), pat.mult_b.clone()),
+        ];
+        if let Some(c) = &pat.accumulate {
+            out.push((self.port_c.clone(), c.clone()));
+        }
+        if let Some(d) = &pat.pre_add {
+            out.push((self.port_d.clone(), d.clone()));
+        }
+        out
+    }
+}
+
+pub type DspShapeFn = fn(&MacroPattern) -> DspShape;
+
+/// Vendor-agnostic default: a single `DSP` cell with `A`/`B`/`C`/`D`/`P`
+/// ports, mirroring the pre-adder + multiply + accumulate shape common to
+/// real DSP slices (e.g. `D + A`, then `* B`, then `+ C`).
+pub fn default_shape(_pat: &MacroPattern) -> DspShape {
+    DspShape {
+        type_name: "DSP",
+        port_a: "A".into(),
+        port_b: "B".into(),
+        port_c: "C".into(),
+        port_d: "D".into(),
+        port_out: "P".into(),
+    }
+}
+
+/// Returns the net identifier driving `pin`, if any.
+fn driving_net(pin: &impl Instantiable<I = PrimitiveCell>) -> Option<Identifier> {
+    pin.driver().and_then(|d| d.get_instance_name())
+}
+
+/// Finds multiplier instances whose cone (inputs, optional pre-adder,
+/// optional accumulate adder) can be replaced by a single macro, provided
+/// the folded intermediate nets have no fanout beyond the cone.
+pub fn find_macros(
+    netlist: &Rc<Netlist<PrimitiveCell>>,
+    analysis: &MultiDiGraph<PrimitiveCell>,
+) -> Vec<MacroPattern> {
+    let mut out = Vec::new();
+
+    for mul in netlist.matches(|i| type_is(i, &["MUL", "MULT"])) {
+        let Some(mul_name) = mul.get_instance_name() else {
+            continue;
+        };
+        let mut inputs = mul.inputs();
+        let (Some(pin_a), Some(pin_b)) = (inputs.next(), inputs.next()) else {
+            continue;
+        };
+        if pin_a.width() != pin_b.width() {
+            continue; // widening/truncating multiply: not a sound macro fold
+        }
+        let Some(mut mult_a) = driving_net(&pin_a) else {
+            continue;
+        };
+        let Some(mult_b) = driving_net(&pin_b) else {
+            continue;
+        };
+
+        let mut consumed = vec![mul_name.clone()];
+
+        let pre_add =
+            sole_producer_adder(netlist, analysis, &mult_a, pin_a.width()).map(|(add, a, d)| {
+                consumed.push(add);
+                mult_a = a;
+                d
+            });
+
+        let (output, accumulate) =
+            match sole_consumer_adder(netlist, analysis, &mul_name, mul.width()) {
+                Some((add_name, c)) => {
+                    consumed.push(add_name.clone());
+                    (add_name, Some(c))
+                }
+                None => (mul_name, None),
+            };
+
+        out.push(MacroPattern {
+            mult_a,
+            mult_b,
+            pre_add,
+            accumulate,
+            output,
+            consumed,
+        });
+    }
+
+    out
+}
+
+/// If `net` feeds exactly one instance and that instance is an adder of the
+/// same width, and `net` has no other fanout, returns the adder's instance
+/// name and the identifier of its other operand net.
+fn sole_consumer_adder(
+    netlist: &Rc<Netlist<PrimitiveCell>>,
+    analysis: &MultiDiGraph<PrimitiveCell>,
+    net: &Identifier,
+    width: u32,
+) -> Option<(Identifier, Identifier)> {
+    if analysis.fanout(net) != 1 {
+        return None;
+    }
+    let add = netlist
+        .matches(|i| type_is(i, &["ADD", "ADDER"]))
+        .find(|i| i.inputs().any(|p| driving_net(&p).as_ref() == Some(net)))?;
+    if add.width() != width {
+        return None; // width mismatch would change the computed result
+    }
+    let add_name = add.get_instance_name()?;
+    let other = add
+        .inputs()
+        .find_map(|p| driving_net(&p).filter(|n| n != net))?;
+    Some((add_name, other))
+}
+
+/// If `net` is itself the output of an adder instance (named `net`, per the
+/// netlist's convention of naming a net after its driving instance) of the
+/// same width, whose result has no fanout beyond the multiplier folding it
+/// in, returns the adder's instance name and its two operand nets — one
+/// becomes the multiplier's new input, the other the macro's pre-adder
+/// operand.
+fn sole_producer_adder(
+    netlist: &Rc<Netlist<PrimitiveCell>>,
+    analysis: &MultiDiGraph<PrimitiveCell>,
+    net: &Identifier,
+    width: u32,
+) -> Option<(Identifier, Identifier, Identifier)> {
+    if analysis.fanout(net) != 1 {
+        return None;
+    }
+    let add = netlist
+        .matches(|i| type_is(i, &["ADD", "ADDER"]))
+        .find(|i| i.get_instance_name().as_ref() == Some(net))?;
+    let add_name = add.get_instance_name()?;
+    let mut inputs = add.inputs();
+    let (Some(pin_a), Some(pin_b)) = (inputs.next(), inputs.next()) else {
+        return None;
+    };
+    if pin_a.width() != width || pin_b.width() != width {
+        return None; // width mismatch would change the computed result
+    }
+    let a = driving_net(&pin_a)?;
+    let b = driving_net(&pin_b)?;
+    Some((add_name, a, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(pre_add: Option<&str>, accumulate: Option<&str>) -> MacroPattern {
+        MacroPattern {
+            mult_a: "a".into(),
+            mult_b: "b".into(),
+            pre_add: pre_add.map(Identifier::from),
+            accumulate: accumulate.map(Identifier::from),
+            output: "out".into(),
+            consumed: vec!["mul0".into()],
+        }
+    }
+
+    // a*b: just the multiply, no pre-adder or accumulate operand.
+    #[test]
+    fn bindings_for_plain_multiply() {
+        let pat = pattern(None, None);
+        let bindings = default_shape(&pat).bindings(&pat);
+        assert_eq!(
+            bindings,
+            vec![("A".into(), "a".into()), ("B".into(), "b".into())]
+        );
+    }
+
+    // a*b+c: multiply plus an accumulate operand on port C.
+    #[test]
+    fn bindings_for_multiply_accumulate() {
+        let pat = pattern(None, Some("c"));
+        let bindings = default_shape(&pat).bindings(&pat);
+        assert_eq!(
+            bindings,
+            vec![
+                ("A".into(), "a".into()),
+                ("B".into(), "b".into()),
+                ("C".into(), "c".into()),
+            ]
+        );
+    }
+
+    // (a+d)*b+c: pre-adder folded onto port D, plus the accumulate operand.
+    #[test]
+    fn bindings_for_pre_adder_multiply_accumulate() {
+        let pat = pattern(Some("d"), Some("c"));
+        let bindings = default_shape(&pat).bindings(&pat);
+        assert_eq!(
+            bindings,
+            vec![
+                ("A".into(), "a".into()),
+                ("B".into(), "b".into()),
+                ("C".into(), "c".into()),
+                ("D".into(), "d".into()),
+            ]
+        );
+    }
+}
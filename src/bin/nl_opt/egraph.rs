@@ -0,0 +1,457 @@
+//! Minimal boolean e-graph used by the `EqSat` pass, kept netlist-agnostic
+//! so the rewrite rules are easy to unit test in isolation.
+
+use safety_net::Identifier;
+use std::collections::{HashMap, HashSet};
+
+pub type EClassId = usize;
+
+/// A single e-node: a primitive boolean op over child e-classes, or a leaf.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ENode {
+    Const(bool),
+    Input(Identifier),
+    And(EClassId, EClassId),
+    Or(EClassId, EClassId),
+    Xor(EClassId, EClassId),
+    Not(EClassId),
+    Mux(EClassId, EClassId, EClassId),
+}
+
+impl ENode {
+    fn map_children(&self, mut f: impl FnMut(EClassId) -> EClassId) -> ENode {
+        match *self {
+            ENode::Const(c) => ENode::Const(c),
+            ENode::Input(ref i) => ENode::Input(i.clone()),
+            ENode::Not(a) => ENode::Not(f(a)),
+            ENode::And(a, b) => {
+                let (a, b) = order(f(a), f(b));
+                ENode::And(a, b)
+            }
+            ENode::Or(a, b) => {
+                let (a, b) = order(f(a), f(b));
+                ENode::Or(a, b)
+            }
+            ENode::Xor(a, b) => {
+                let (a, b) = order(f(a), f(b));
+                ENode::Xor(a, b)
+            }
+            ENode::Mux(s, a, b) => ENode::Mux(f(s), f(a), f(b)),
+        }
+    }
+}
+
+/// Canonical ordering for commutative ops so `a & b` and `b & a` hash-cons
+/// to the same e-node.
+fn order(a: EClassId, b: EClassId) -> (EClassId, EClassId) {
+    if a <= b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+#[derive(Default)]
+struct EClass {
+    nodes: HashSet<ENode>,
+}
+
+/// A congruence-closed e-graph over [`ENode`]s.
+pub struct EGraph {
+    parent: Vec<EClassId>,
+    classes: Vec<EClass>,
+    hashcons: HashMap<ENode, EClassId>,
+}
+
+impl Default for EGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EGraph {
+    pub fn new() -> Self {
+        EGraph {
+            parent: Vec::new(),
+            classes: Vec::new(),
+            hashcons: HashMap::new(),
+        }
+    }
+
+    pub fn find(&mut self, id: EClassId) -> EClassId {
+        if self.parent[id] == id {
+            return id;
+        }
+        let root = self.find(self.parent[id]);
+        self.parent[id] = root;
+        root
+    }
+
+    /// Adds an e-node, canonicalizing its children first, and returns the
+    /// e-class it lives in (reusing an existing class via hash-consing when
+    /// a structurally identical node already exists).
+    pub fn add(&mut self, node: ENode) -> EClassId {
+        let node = node.map_children(|c| self.find(c));
+        if let Some(&id) = self.hashcons.get(&node) {
+            return self.find(id);
+        }
+        let id = self.classes.len();
+        self.parent.push(id);
+        let mut class = EClass::default();
+        class.nodes.insert(node.clone());
+        self.classes.push(class);
+        self.hashcons.insert(node, id);
+        id
+    }
+
+    /// Unions two e-classes, merging their node sets. Returns `true` if the
+    /// union changed anything (i.e. the classes weren't already equal).
+    pub fn union(&mut self, a: EClassId, b: EClassId) -> bool {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+        self.parent[b] = a;
+        let moved = std::mem::take(&mut self.classes[b].nodes);
+        self.classes[a].nodes.extend(moved);
+        true
+    }
+
+    /// Recanonicalizes every node's children onto their class's current
+    /// root and rebuilds the hashcons lookup from that, unioning any classes
+    /// that now hold the same canonical node (congruence closure) and
+    /// repeating until none do. Returns `true` if any classes were merged.
+    ///
+    /// Without this, a class absorbed by `union` keeps an empty `nodes` set
+    /// forever, so lookups keyed on its id (root indexing, `is_const`,
+    /// `single_def`) silently see nothing even though its content lives on
+    /// under the new root.
+    fn rebuild_hashcons(&mut self) -> bool {
+        let mut merged = false;
+        loop {
+            let mut canonical: Vec<HashSet<ENode>> =
+                (0..self.classes.len()).map(|_| HashSet::new()).collect();
+            for id in 0..self.classes.len() {
+                let root = self.find(id);
+                let nodes: Vec<ENode> = self.classes[id].nodes.iter().cloned().collect();
+                for n in nodes {
+                    canonical[root].insert(n.map_children(|c| self.find(c)));
+                }
+            }
+            for (id, nodes) in canonical.into_iter().enumerate() {
+                self.classes[id].nodes = nodes;
+            }
+
+            self.hashcons.clear();
+            let mut congruent = Vec::new();
+            for id in 0..self.classes.len() {
+                if self.find(id) != id {
+                    continue;
+                }
+                for n in self.classes[id].nodes.iter().cloned() {
+                    if let Some(other) = self.hashcons.insert(n, id) {
+                        congruent.push((other, id));
+                    }
+                }
+            }
+            if congruent.is_empty() {
+                return merged;
+            }
+            for (a, b) in congruent {
+                merged |= self.union(a, b);
+            }
+        }
+    }
+
+    /// Applies the boolean rewrite rule set to every e-node in the graph
+    /// once, returning the unions it produced (not yet applied).
+    fn apply_rules_once(&mut self) -> Vec<(EClassId, EClassId)> {
+        let mut unions = Vec::new();
+        let snapshot: Vec<(EClassId, ENode)> = self
+            .classes
+            .iter()
+            .enumerate()
+            .flat_map(|(id, c)| c.nodes.iter().cloned().map(move |n| (id, n)))
+            .collect();
+
+        for (id, node) in snapshot {
+            for rhs in rewrite(self, &node) {
+                unions.push((id, rhs));
+            }
+        }
+        unions
+    }
+
+    /// Runs congruence closure + rewriting to a fixpoint, or until
+    /// `max_iters` rounds or `node_budget` e-nodes have been created,
+    /// whichever comes first.
+    pub fn saturate(&mut self, max_iters: usize, node_budget: usize) -> usize {
+        let mut iters = 0;
+        loop {
+            if iters >= max_iters || self.hashcons.len() >= node_budget {
+                break;
+            }
+            let unions = self.apply_rules_once();
+            let mut changed = false;
+            for (a, b) in unions {
+                changed |= self.union(a, b);
+            }
+            changed |= self.rebuild_hashcons();
+            iters += 1;
+            if !changed {
+                break;
+            }
+        }
+        iters
+    }
+
+    pub fn class_nodes(&self, id: EClassId) -> Vec<ENode> {
+        self.classes[id].nodes.iter().cloned().collect()
+    }
+
+    pub fn num_classes(&self) -> usize {
+        self.classes.len()
+    }
+}
+
+/// Returns e-classes that `node` should be unioned with under the rewrite
+/// rule set (constant folding, idempotence, self-inverse, MUX
+/// simplification). `eg` is used read-only to look up sibling nodes, so a
+/// rule can only union `node` with a class that already exists; rules that
+/// build new terms, such as associativity and De Morgan, are not applied.
+fn rewrite(eg: &EGraph, node: &ENode) -> Vec<EClassId> {
+    let mut out = Vec::new();
+    match *node {
+        ENode::And(a, b) if a == b => out.push(a), // x & x = x
+        ENode::Or(a, b) if a == b => out.push(a),  // x | x = x
+        ENode::Xor(a, b) if a == b => {
+            if let Some(z) = find_const(eg, false) {
+                out.push(z); // x ^ x = 0
+            }
+        }
+        ENode::And(a, b) => {
+            if is_const(eg, a, false) || is_const(eg, b, false) {
+                if let Some(z) = find_const(eg, false) {
+                    out.push(z); // x & 0 = 0
+                }
+            } else if is_const(eg, a, true) {
+                out.push(b); // 1 & x = x
+            } else if is_const(eg, b, true) {
+                out.push(a); // x & 1 = x
+            }
+        }
+        ENode::Or(a, b) => {
+            if is_const(eg, a, true) || is_const(eg, b, true) {
+                if let Some(o) = find_const(eg, true) {
+                    out.push(o); // x | 1 = 1
+                }
+            } else if is_const(eg, a, false) {
+                out.push(b); // 0 | x = x
+            } else if is_const(eg, b, false) {
+                out.push(a); // x | 0 = x
+            }
+        }
+        ENode::Xor(a, b) => {
+            if is_const(eg, a, false) {
+                out.push(b); // 0 ^ x = x
+            } else if is_const(eg, b, false) {
+                out.push(a); // x ^ 0 = x
+            }
+        }
+        ENode::Not(a) => {
+            if let ENode::Not(b) = single_def(eg, a) {
+                out.push(b); // !!x = x
+            }
+        }
+        ENode::Mux(s, a, b) => {
+            if a == b {
+                out.push(a); // mux(s, x, x) = x
+            } else if is_const(eg, s, true) {
+                out.push(a); // mux(1, a, b) = a
+            } else if is_const(eg, s, false) {
+                out.push(b); // mux(0, a, b) = b
+            }
+        }
+        _ => {}
+    }
+    out
+}
+
+fn is_const(eg: &EGraph, id: EClassId, value: bool) -> bool {
+    eg.classes[id].nodes.contains(&ENode::Const(value))
+}
+
+fn find_const(eg: &EGraph, value: bool) -> Option<EClassId> {
+    eg.hashcons.get(&ENode::Const(value)).copied()
+}
+
+/// Returns one e-node of `id`'s class, whichever its node set yields first,
+/// or `Const(false)` for an empty class (used for single-hop rules like
+/// double-negation). The other nodes of the class are not looked at.
+fn single_def(eg: &EGraph, id: EClassId) -> ENode {
+    eg.classes[id]
+        .nodes
+        .iter()
+        .next()
+        .cloned()
+        .unwrap_or(ENode::Const(false))
+}
+
+fn operand_classes(node: &ENode) -> Vec<EClassId> {
+    match *node {
+        ENode::Const(_) | ENode::Input(_) => vec![],
+        ENode::Not(a) => vec![a],
+        ENode::And(a, b) | ENode::Or(a, b) | ENode::Xor(a, b) => vec![a, b],
+        ENode::Mux(s, a, b) => vec![s, a, b],
+    }
+}
+
+/// A class's currently-best implementation: the chosen e-node plus the set
+/// of e-classes its sub-DAG materializes (itself included).
+struct Best {
+    node: ENode,
+    materializes: HashSet<EClassId>,
+}
+
+/// Greedy global-DAG extraction: picks one e-node per e-class to minimize
+/// deduplicated DAG cost rather than tree cost, so shared sub-circuits are
+/// priced once instead of once per consumer. A heuristic — optimal DAG
+/// extraction is NP-hard.
+pub fn dag_extract(
+    eg: &EGraph,
+    roots: &[EClassId],
+    cost: impl Fn(&ENode) -> u32,
+) -> Vec<(EClassId, ENode)> {
+    let n = eg.num_classes();
+    let mut best: Vec<Option<Best>> = (0..n).map(|_| None).collect();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for id in 0..n {
+            for node in eg.class_nodes(id) {
+                let operands = operand_classes(&node);
+                if !operands.iter().all(|c| best[*c].is_some()) {
+                    continue;
+                }
+
+                let mut materializes: HashSet<EClassId> = HashSet::new();
+                materializes.insert(id);
+                for c in &operands {
+                    materializes.extend(best[*c].as_ref().unwrap().materializes.iter().copied());
+                }
+
+                let score: u32 = materializes
+                    .iter()
+                    .map(|&cid| {
+                        if cid == id {
+                            cost(&node)
+                        } else {
+                            cost(&best[cid].as_ref().unwrap().node)
+                        }
+                    })
+                    .sum();
+
+                let adopt = match &best[id] {
+                    None => true,
+                    Some(cur) => {
+                        let cur_score: u32 = cur
+                            .materializes
+                            .iter()
+                            .map(|&cid| cost(&best[cid].as_ref().unwrap().node))
+                            .sum();
+                        (score, materializes.len()) < (cur_score, cur.materializes.len())
+                    }
+                };
+                if adopt {
+                    best[id] = Some(Best { node, materializes });
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    let mut emitted: HashSet<EClassId> = HashSet::new();
+    let mut out = Vec::new();
+    for &root in roots {
+        if let Some(b) = &best[root] {
+            for &cid in &b.materializes {
+                if emitted.insert(cid) {
+                    out.push((cid, best[cid].as_ref().unwrap().node.clone()));
+                }
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saturate_folds_and_with_zero() {
+        let mut eg = EGraph::new();
+        let x = eg.add(ENode::Input("x".into()));
+        let zero = eg.add(ENode::Const(false));
+        let and = eg.add(ENode::And(x, zero));
+
+        eg.saturate(16, 1_000);
+
+        assert_eq!(eg.find(and), eg.find(zero));
+    }
+
+    #[test]
+    fn saturate_cancels_double_negation() {
+        let mut eg = EGraph::new();
+        let x = eg.add(ENode::Input("x".into()));
+        let not_x = eg.add(ENode::Not(x));
+        let not_not_x = eg.add(ENode::Not(not_x));
+
+        eg.saturate(16, 1_000);
+
+        assert_eq!(eg.find(not_not_x), eg.find(x));
+    }
+
+    #[test]
+    fn saturate_reaches_fixpoint_without_hitting_the_iteration_cap() {
+        let mut eg = EGraph::new();
+        let x = eg.add(ENode::Input("x".into()));
+        let one = eg.add(ENode::Const(true));
+        eg.add(ENode::And(x, one)); // x & 1 = x
+
+        let iters = eg.saturate(16, 1_000);
+
+        assert!(iters < 16);
+    }
+
+    #[test]
+    fn dag_extract_shares_a_common_subexpression_across_two_roots() {
+        let mut eg = EGraph::new();
+        let a = eg.add(ENode::Input("a".into()));
+        let b = eg.add(ENode::Input("b".into()));
+        let shared = eg.add(ENode::And(a, b));
+        let out1 = eg.add(ENode::Not(shared));
+        let out2 = eg.add(ENode::Or(shared, a));
+
+        let extracted = dag_extract(&eg, &[out1, out2], |node| match node {
+            ENode::Const(_) | ENode::Input(_) => 0,
+            _ => 1,
+        });
+
+        // The shared AND is extracted exactly once, not once per consumer.
+        let and_count = extracted
+            .iter()
+            .filter(|(_, n)| matches!(n, ENode::And(..)))
+            .count();
+        assert_eq!(and_count, 1);
+    }
+
+    #[test]
+    fn dag_extract_with_no_roots_extracts_nothing() {
+        let mut eg = EGraph::new();
+        eg.add(ENode::Input("a".into()));
+        let extracted = dag_extract(&eg, &[], |_| 0);
+        assert!(extracted.is_empty());
+    }
+}
@@ -0,0 +1,307 @@
+//! Structural equivalence smoketest: proves (up to a bounded exhaustive
+//! check) that a netlist computes the same function as a golden reference.
+//! Sequential designs are cut at register boundaries rather than modeled
+//! across clock cycles.
+
+use eqmap::netlist::PrimitiveCell;
+use safety_net::{Identifier, Instantiable, Netlist};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+/// Exhaustive checks only make sense up to a handful of free variables;
+/// beyond this the smoketest reports that it can't exhaustively cover the
+/// design rather than silently sampling (and potentially missing a bug).
+pub const MAX_FREE_INPUTS: u32 = 20;
+
+/// Either the two netlists agree on every input assignment checked, or the
+/// first disagreement found, including a reproducing assignment.
+pub enum Verdict {
+    Equivalent {
+        assignments_checked: u64,
+    },
+    Counterexample {
+        output: Identifier,
+        assignment: Vec<(Identifier, bool)>,
+    },
+    TooLarge {
+        free_inputs: usize,
+    },
+    PortMismatch {
+        inputs_only_in_netlist: Vec<Identifier>,
+        inputs_only_in_golden: Vec<Identifier>,
+        outputs_only_in_netlist: Vec<Identifier>,
+        outputs_only_in_golden: Vec<Identifier>,
+    },
+}
+
+fn gate_op(type_name: &str) -> Option<&'static str> {
+    match type_name {
+        "AND" | "AND2" => Some("and"),
+        "OR" | "OR2" => Some("or"),
+        "XOR" | "XOR2" => Some("xor"),
+        "INV" | "NOT" => Some("not"),
+        "MUX" | "MUX2" => Some("mux"),
+        _ => None,
+    }
+}
+
+/// Primary inputs, plus one pseudo-input per register (its Q output, which
+/// is what downstream combinational logic actually sees).
+fn free_inputs(netlist: &Rc<Netlist<PrimitiveCell>>) -> Vec<Identifier> {
+    let mut names: Vec<Identifier> = netlist.primary_inputs().collect();
+    names.extend(
+        netlist
+            .matches(|i| i.is_seq())
+            .filter_map(|r| r.get_instance_name()),
+    );
+    names
+}
+
+/// Primary outputs, plus one pseudo-output per register (its D input,
+/// which is the combinational logic that actually needs checking).
+fn checked_outputs(netlist: &Rc<Netlist<PrimitiveCell>>) -> Vec<Identifier> {
+    let mut names: Vec<Identifier> = netlist.primary_outputs().collect();
+    for reg in netlist.matches(|i| i.is_seq()) {
+        if let Some(driver) = reg
+            .inputs()
+            .find_map(|p| p.driver().and_then(|d| d.get_instance_name()))
+        {
+            names.push(driver);
+        }
+    }
+    names
+}
+
+/// Evaluates the net `name` under `assignment`, recursively evaluating
+/// whatever primitive drives it. Nets with no recognized combinational
+/// driver (primary inputs, register outputs) must already be present in
+/// `assignment`.
+fn eval(
+    netlist: &Rc<Netlist<PrimitiveCell>>,
+    name: &Identifier,
+    assignment: &HashMap<Identifier, bool>,
+    memo: &mut HashMap<Identifier, bool>,
+) -> bool {
+    if let Some(&v) = memo.get(name) {
+        return v;
+    }
+    if let Some(&v) = assignment.get(name) {
+        memo.insert(name.clone(), v);
+        return v;
+    }
+
+    let inst = netlist
+        .matches(|i| i.get_instance_name().as_ref() == Some(name))
+        .next();
+
+    let value = match inst {
+        None => false,
+        Some(inst) => {
+            let operand_names: Vec<Identifier> = inst
+                .inputs()
+                .filter_map(|p| p.driver().and_then(|d| d.get_instance_name()))
+                .collect();
+            let operands: Vec<bool> = operand_names
+                .iter()
+                .map(|n| eval(netlist, n, assignment, memo))
+                .collect();
+            let get = |i: usize| operands.get(i).copied().unwrap_or(false);
+
+            match gate_op(inst.type_name()) {
+                Some("and") => get(0) && get(1),
+                Some("or") => get(0) || get(1),
+                Some("xor") => get(0) ^ get(1),
+                Some("not") => !get(0),
+                Some("mux") => {
+                    if get(0) {
+                        get(1)
+                    } else {
+                        get(2)
+                    }
+                }
+                _ => false,
+            }
+        }
+    };
+
+    memo.insert(name.clone(), value);
+    value
+}
+
+/// Returns a [`Verdict::PortMismatch`] if `netlist` and `golden` disagree on
+/// their free inputs or checked outputs, so `check` doesn't silently hold a
+/// port present in only one of the two at its default (`false`) value.
+fn port_mismatch(
+    inputs: &HashSet<Identifier>,
+    golden_inputs: &HashSet<Identifier>,
+    outputs: &HashSet<Identifier>,
+    golden_outputs: &HashSet<Identifier>,
+) -> Option<Verdict> {
+    if inputs == golden_inputs && outputs == golden_outputs {
+        return None;
+    }
+    Some(Verdict::PortMismatch {
+        inputs_only_in_netlist: inputs.difference(golden_inputs).cloned().collect(),
+        inputs_only_in_golden: golden_inputs.difference(inputs).cloned().collect(),
+        outputs_only_in_netlist: outputs.difference(golden_outputs).cloned().collect(),
+        outputs_only_in_golden: golden_outputs.difference(outputs).cloned().collect(),
+    })
+}
+
+/// Enumerates every assignment to `shared_inputs` and compares `eval_a`
+/// against `eval_b` over `shared_outputs`, stopping at the first
+/// disagreement. Factored out of `check` so the comparison itself can be
+/// exercised directly, without a `Netlist` on either side.
+fn compare(
+    shared_inputs: &[Identifier],
+    shared_outputs: &[Identifier],
+    mut eval_a: impl FnMut(&Identifier, &HashMap<Identifier, bool>) -> bool,
+    mut eval_b: impl FnMut(&Identifier, &HashMap<Identifier, bool>) -> bool,
+) -> Verdict {
+    let n = shared_inputs.len() as u32;
+    let mut checked = 0u64;
+    for bits in 0..(1u64 << n) {
+        let assignment: HashMap<Identifier, bool> = shared_inputs
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.clone(), (bits >> i) & 1 == 1))
+            .collect();
+
+        for output in shared_outputs {
+            let a = eval_a(output, &assignment);
+            let b = eval_b(output, &assignment);
+            if a != b {
+                return Verdict::Counterexample {
+                    output: output.clone(),
+                    assignment: shared_inputs
+                        .iter()
+                        .map(|name| (name.clone(), assignment[name]))
+                        .collect(),
+                };
+            }
+        }
+        checked += 1;
+    }
+
+    Verdict::Equivalent {
+        assignments_checked: checked,
+    }
+}
+
+/// Checks combinational equivalence between `netlist` and `golden`,
+/// enumerating every assignment to their shared free inputs (primary
+/// inputs plus register outputs) and comparing every checked output
+/// (primary outputs plus register inputs) between the two.
+pub fn check(netlist: &Rc<Netlist<PrimitiveCell>>, golden: &Rc<Netlist<PrimitiveCell>>) -> Verdict {
+    let inputs: HashSet<Identifier> = free_inputs(netlist).into_iter().collect();
+    let golden_inputs: HashSet<Identifier> = free_inputs(golden).into_iter().collect();
+    let outputs: HashSet<Identifier> = checked_outputs(netlist).into_iter().collect();
+    let golden_outputs: HashSet<Identifier> = checked_outputs(golden).into_iter().collect();
+
+    if let Some(verdict) = port_mismatch(&inputs, &golden_inputs, &outputs, &golden_outputs) {
+        return verdict;
+    }
+
+    let shared_inputs: Vec<Identifier> = inputs.into_iter().collect();
+    if shared_inputs.len() > MAX_FREE_INPUTS as usize {
+        return Verdict::TooLarge {
+            free_inputs: shared_inputs.len(),
+        };
+    }
+    let shared_outputs: Vec<Identifier> = outputs.into_iter().collect();
+
+    compare(
+        &shared_inputs,
+        &shared_outputs,
+        |name, assignment| eval(netlist, name, assignment, &mut HashMap::new()),
+        |name, assignment| eval(golden, name, assignment, &mut HashMap::new()),
+    )
+}
+
+// `check` itself needs a real `Netlist<PrimitiveCell>` fixture (through
+// `free_inputs`/`checked_outputs`/`eval`), which this crate has no way to
+// construct outside of parsing real Verilog. `compare` and `port_mismatch`
+// carry the actual pass/fail logic and take plain functions and sets, so
+// they're tested directly here instead. The register-cut behavior (a reg's
+// Q becomes a free input, its D becomes a checked output) lives entirely in
+// `free_inputs`/`checked_outputs` and isn't covered by these tests.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(s: &str) -> Identifier {
+        s.into()
+    }
+
+    fn and_fn(_: &Identifier, assignment: &HashMap<Identifier, bool>) -> bool {
+        assignment[&id("a")] && assignment[&id("b")]
+    }
+
+    fn or_fn(_: &Identifier, assignment: &HashMap<Identifier, bool>) -> bool {
+        assignment[&id("a")] || assignment[&id("b")]
+    }
+
+    #[test]
+    fn compare_reports_equivalent_for_matching_functions() {
+        let inputs = vec![id("a"), id("b")];
+        let outputs = vec![id("y")];
+        let verdict = compare(&inputs, &outputs, and_fn, and_fn);
+        assert!(matches!(
+            verdict,
+            Verdict::Equivalent {
+                assignments_checked: 4
+            }
+        ));
+    }
+
+    #[test]
+    fn compare_finds_a_real_counterexample() {
+        let inputs = vec![id("a"), id("b")];
+        let outputs = vec![id("y")];
+        let verdict = compare(&inputs, &outputs, and_fn, or_fn);
+        match verdict {
+            Verdict::Counterexample { output, assignment } => {
+                assert_eq!(output, id("y"));
+                // a=true, b=false is the first assignment (bits=0b01) where
+                // AND and OR disagree.
+                assert_eq!(assignment, vec![(id("a"), true), (id("b"), false)]);
+            }
+            _ => panic!("expected a counterexample, got an equivalent verdict"),
+        }
+    }
+
+    #[test]
+    fn port_mismatch_reports_the_differing_ports() {
+        let inputs: HashSet<Identifier> = [id("a"), id("b")].into_iter().collect();
+        let golden_inputs: HashSet<Identifier> = [id("a")].into_iter().collect();
+        let outputs: HashSet<Identifier> = [id("y")].into_iter().collect();
+
+        match port_mismatch(&inputs, &golden_inputs, &outputs, &outputs) {
+            Some(Verdict::PortMismatch {
+                inputs_only_in_netlist,
+                inputs_only_in_golden,
+                outputs_only_in_netlist,
+                outputs_only_in_golden,
+            }) => {
+                assert_eq!(inputs_only_in_netlist, vec![id("b")]);
+                assert!(inputs_only_in_golden.is_empty());
+                assert!(outputs_only_in_netlist.is_empty());
+                assert!(outputs_only_in_golden.is_empty());
+            }
+            _ => panic!("expected a port mismatch"),
+        }
+    }
+
+    #[test]
+    fn port_mismatch_is_none_when_ports_line_up() {
+        let ports: HashSet<Identifier> = [id("a"), id("b")].into_iter().collect();
+        assert!(port_mismatch(&ports, &ports, &ports, &ports).is_none());
+    }
+
+    #[test]
+    fn gate_op_recognizes_known_primitive_names() {
+        assert_eq!(gate_op("AND2"), Some("and"));
+        assert_eq!(gate_op("MUX"), Some("mux"));
+        assert_eq!(gate_op("FLIPFLOP"), None);
+    }
+}
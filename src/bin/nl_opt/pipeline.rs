@@ -0,0 +1,135 @@
+//! Pass pipeline grammar: `clean,(eqsat,clean)*3` runs `clean` once, then
+//! loops `eqsat,clean` up to 3 rounds (or fewer, once the group reports no
+//! further change). Lets a flow run to convergence instead of the strictly
+//! linear, run-each-pass-once model the CLI used to have.
+
+use eqmap::pass::{Error, Pass};
+use safety_net::Netlist;
+use std::rc::Rc;
+
+/// Outcome of a pass that can report whether it mutated the netlist, so a
+/// `(...)*N` group knows when it has reached a fixed point.
+pub struct PassOutcome {
+    pub message: String,
+    pub changed: bool,
+}
+
+/// Passes worth looping inside a pipeline group implement this in addition
+/// to `Pass`. Passes that only report (e.g. `ReportSccs`) have no notion of
+/// "changed" and don't need it.
+pub trait FixedPointPass: Pass {
+    fn run_tracked(&self, netlist: &Rc<Netlist<Self::I>>) -> Result<PassOutcome, Error>;
+}
+
+/// One stage of a parsed pipeline: a single pass, or a parenthesized group
+/// repeated up to `max_rounds` times (or until no pass in the group reports
+/// a change, whichever comes first).
+pub enum Stage<P> {
+    Single(P),
+    Group { passes: Vec<P>, max_rounds: usize },
+}
+
+/// Parses a pipeline expression like `"clean,(eqsat,clean)*3"` into stages,
+/// using `parse_token` to turn each bare pass name into a `P`.
+pub fn parse<P>(
+    src: &str,
+    parse_token: impl Fn(&str) -> Result<P, String>,
+) -> Result<Vec<Stage<P>>, String> {
+    let mut stages = Vec::new();
+    let mut rest = src.trim();
+
+    while !rest.is_empty() {
+        if let Some(body_and_rest) = rest.strip_prefix('(') {
+            let close = body_and_rest
+                .find(')')
+                .ok_or_else(|| format!("unterminated group in pipeline '{src}'"))?;
+            let (body, after_paren) = body_and_rest.split_at(close);
+            let after_paren = &after_paren[1..];
+
+            let passes = body
+                .split(',')
+                .map(|t| parse_token(t.trim()))
+                .collect::<Result<Vec<_>, _>>()?;
+            if passes.is_empty() {
+                return Err(format!("empty group in pipeline '{src}'"));
+            }
+
+            let after_star = after_paren.trim_start();
+            let (max_rounds, after_count) = match after_star.strip_prefix('*') {
+                Some(digits_and_rest) => {
+                    let end = digits_and_rest
+                        .find(|c: char| !c.is_ascii_digit())
+                        .unwrap_or(digits_and_rest.len());
+                    let (digits, rem) = digits_and_rest.split_at(end);
+                    let n: usize = digits
+                        .parse()
+                        .map_err(|_| format!("invalid repeat count in pipeline '{src}'"))?;
+                    (n, rem)
+                }
+                None => (usize::MAX, after_star),
+            };
+
+            stages.push(Stage::Group { passes, max_rounds });
+            rest = after_count
+                .trim_start()
+                .trim_start_matches(',')
+                .trim_start();
+        } else {
+            let end = rest.find([',', '(']).unwrap_or(rest.len());
+            let (tok, after) = rest.split_at(end);
+            stages.push(Stage::Single(parse_token(tok.trim())?));
+            rest = after.trim_start_matches(',').trim_start();
+        }
+    }
+
+    Ok(stages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(src: &str) -> Result<Vec<Stage<String>>, String> {
+        parse(src, |t| Ok(t.to_string()))
+    }
+
+    #[test]
+    fn parses_a_single_pass() {
+        let stages = parse_str("clean").unwrap();
+        assert_eq!(stages.len(), 1);
+        assert!(matches!(&stages[0], Stage::Single(p) if p == "clean"));
+    }
+
+    #[test]
+    fn parses_a_mix_of_singles_and_groups() {
+        let stages = parse_str("clean,(eqsat,clean)*3").unwrap();
+        assert_eq!(stages.len(), 2);
+        assert!(matches!(&stages[0], Stage::Single(p) if p == "clean"));
+        match &stages[1] {
+            Stage::Group { passes, max_rounds } => {
+                assert_eq!(passes, &["eqsat", "clean"]);
+                assert_eq!(*max_rounds, 3);
+            }
+            Stage::Single(_) => panic!("expected a group"),
+        }
+    }
+
+    #[test]
+    fn group_without_a_star_count_leaves_max_rounds_unset() {
+        let stages = parse_str("(eqsat,clean)").unwrap();
+        match &stages[0] {
+            Stage::Group { max_rounds, .. } => assert_eq!(*max_rounds, usize::MAX),
+            Stage::Single(_) => panic!("expected a group"),
+        }
+    }
+
+    #[test]
+    fn rejects_an_unterminated_group() {
+        assert!(parse_str("(eqsat,clean").is_err());
+    }
+
+    #[test]
+    fn rejects_a_bad_repeat_count() {
+        assert!(parse_str("(clean)*x").is_err());
+    }
+}